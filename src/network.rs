@@ -5,13 +5,358 @@
 use crate::parse_ip_address;
 use async_trait::async_trait;
 use futures::future::join_all;
-use slog::info;
+use futures::Stream;
+use rand::RngCore;
+use slog::{info, warn};
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::fmt;
 use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, oneshot, Mutex, Notify};
+use tokio_util::sync::CancellationToken;
+
+/// Identifies an in-flight request on a multiplexed connection so its response
+/// can be routed back to the caller awaiting it.
+pub type RequestId = u32;
+
+/// Default cap on a single frame's payload size, used when a `TCPManager`
+/// is constructed without an explicit override via [`TCPManager::with_max_frame_size`].
+pub const DEFAULT_MAX_FRAME_SIZE: u32 = 16 * 1024 * 1024;
+
+/// Default cap on how long `close` waits for outstanding request/response
+/// exchanges to finish before giving up, used unless overridden via
+/// [`TCPManager::with_drain_timeout`].
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Errors produced while framing/deframing messages on the wire.
+#[derive(Debug)]
+pub enum FramingError {
+    /// The 4-byte length prefix declared a payload larger than the configured maximum.
+    FrameTooLarge { declared: u32, max: u32 },
+    /// The connection was closed before a full frame (header or body) was read.
+    UnexpectedEof,
+}
+
+impl fmt::Display for FramingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FramingError::FrameTooLarge { declared, max } => write!(
+                f,
+                "frame of {} bytes exceeds max frame size of {} bytes",
+                declared, max
+            ),
+            FramingError::UnexpectedEof => {
+                write!(f, "connection closed before a full frame was received")
+            }
+        }
+    }
+}
+
+impl Error for FramingError {}
+
+/// Writes `data` to `writer` prefixed with its length as a 4-byte big-endian `u32`.
+async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    data: &[u8],
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let len = u32::try_from(data.len())?;
+    writer.write_all(&len.to_be_bytes()).await?;
+    writer.write_all(data).await?;
+    Ok(())
+}
+
+/// Reads one length-prefixed frame from `reader`, rejecting declared lengths above
+/// `max_frame_size` and reporting any EOF that cuts a frame short.
+async fn read_frame<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    max_frame_size: u32,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let mut len_buf = [0u8; 4];
+    match reader.read_exact(&mut len_buf).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+            return Err(Box::new(FramingError::UnexpectedEof));
+        }
+        Err(e) => return Err(Box::new(e)),
+    }
+    let len = u32::from_be_bytes(len_buf);
+    if len > max_frame_size {
+        return Err(Box::new(FramingError::FrameTooLarge {
+            declared: len,
+            max: max_frame_size,
+        }));
+    }
+    let mut buffer = vec![0u8; len as usize];
+    match reader.read_exact(&mut buffer).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+            return Err(Box::new(FramingError::UnexpectedEof));
+        }
+        Err(e) => return Err(Box::new(e)),
+    }
+    Ok(buffer)
+}
+
+/// Errors produced while decoding a multiplexed request/response envelope.
+#[derive(Debug)]
+pub struct EnvelopeTooShort;
+
+impl fmt::Display for EnvelopeTooShort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "envelope shorter than the 5-byte request header")
+    }
+}
+
+impl Error for EnvelopeTooShort {}
+
+/// Prefixes `payload` with the `(request_id, is_response)` header used to
+/// multiplex several in-flight requests over one connection.
+fn encode_envelope(request_id: RequestId, is_response: bool, payload: &[u8]) -> Vec<u8> {
+    let mut envelope = Vec::with_capacity(5 + payload.len());
+    envelope.extend_from_slice(&request_id.to_be_bytes());
+    envelope.push(is_response as u8);
+    envelope.extend_from_slice(payload);
+    envelope
+}
+
+/// Splits a decoded frame back into its `(request_id, is_response, payload)` parts.
+fn decode_envelope(
+    envelope: Vec<u8>,
+) -> Result<(RequestId, bool, Vec<u8>), Box<dyn Error + Send + Sync>> {
+    if envelope.len() < 5 {
+        return Err(Box::new(EnvelopeTooShort));
+    }
+    let request_id = u32::from_be_bytes(envelope[0..4].try_into().unwrap());
+    let is_response = envelope[4] != 0;
+    Ok((request_id, is_response, envelope[5..].to_vec()))
+}
+
+/// Encodes `data` into an envelope, seals it under `session_key` if one was
+/// negotiated, and writes the resulting frame to `writer`. Shared by every
+/// place that puts a multiplexed request or response on the wire:
+/// `TCPManager::send_and_wait`'s request, `send_pooled`'s fire-and-forget
+/// request, and `respond`'s reply.
+async fn write_envelope<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    session_key: &Option<[u8; 32]>,
+    request_id: RequestId,
+    is_response: bool,
+    data: &[u8],
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let envelope = encode_envelope(request_id, is_response, data);
+    let frame = match session_key {
+        Some(key) => seal(key, &envelope)?,
+        None => envelope,
+    };
+    write_frame(writer, &frame).await
+}
+
+/// One long-lived, multiplexed connection to a peer: a shared writer half plus
+/// the table of requests awaiting a response, drained by a background reader task.
+struct Connection {
+    writer: Mutex<OwnedWriteHalf>,
+    pending: Arc<Mutex<HashMap<RequestId, oneshot::Sender<Vec<u8>>>>>,
+    session_key: Option<[u8; 32]>,
+}
+
+/// RAII tracker for one in-flight request/response exchange: increments a
+/// shared counter on creation and decrements it (waking anyone draining) on
+/// drop, regardless of which path out of the exchange is taken.
+struct OutstandingGuard {
+    outstanding: Arc<AtomicU32>,
+    drain_notify: Arc<Notify>,
+}
+
+impl OutstandingGuard {
+    fn new(outstanding: Arc<AtomicU32>, drain_notify: Arc<Notify>) -> Self {
+        outstanding.fetch_add(1, Ordering::SeqCst);
+        OutstandingGuard {
+            outstanding,
+            drain_notify,
+        }
+    }
+}
+
+impl Drop for OutstandingGuard {
+    fn drop(&mut self) {
+        if self.outstanding.fetch_sub(1, Ordering::SeqCst) == 1 {
+            // `notify_one` (unlike `notify_waiters`) stores a permit when there is
+            // no waiter registered yet, so a guard dropping between `close`'s count
+            // check and its `.await` on `notified()` still wakes it instead of
+            // stalling until `drain_timeout` elapses.
+            self.drain_notify.notify_one();
+        }
+    }
+}
+
+const HANDSHAKE_MESSAGE_LEN: usize = 128;
+
+/// Wire format exchanged by both sides immediately after connect/accept: a
+/// node's ed25519 public key, a fresh nonce, and a signature proving both
+/// the private key and knowledge of the shared `network_secret`.
+struct HandshakeMessage {
+    public_key: [u8; 32],
+    nonce: [u8; 32],
+    signature: [u8; 64],
+}
+
+impl HandshakeMessage {
+    fn to_bytes(&self) -> [u8; HANDSHAKE_MESSAGE_LEN] {
+        let mut buf = [0u8; HANDSHAKE_MESSAGE_LEN];
+        buf[0..32].copy_from_slice(&self.public_key);
+        buf[32..64].copy_from_slice(&self.nonce);
+        buf[64..128].copy_from_slice(&self.signature);
+        buf
+    }
+
+    fn from_bytes(buf: &[u8; HANDSHAKE_MESSAGE_LEN]) -> Self {
+        let mut public_key = [0u8; 32];
+        let mut nonce = [0u8; 32];
+        let mut signature = [0u8; 64];
+        public_key.copy_from_slice(&buf[0..32]);
+        nonce.copy_from_slice(&buf[32..64]);
+        signature.copy_from_slice(&buf[64..128]);
+        HandshakeMessage {
+            public_key,
+            nonce,
+            signature,
+        }
+    }
+}
+
+/// A node's durable ed25519 identity plus the pre-shared secret and peer
+/// allowlist that together gate which nodes may join this cluster's traffic.
+pub struct NodeIdentity {
+    signing_key: ed25519_dalek::SigningKey,
+    network_secret: [u8; 32],
+    trusted_keys: Mutex<HashSet<[u8; 32]>>,
+}
+
+impl NodeIdentity {
+    pub fn new(
+        signing_key: ed25519_dalek::SigningKey,
+        network_secret: [u8; 32],
+        trusted_keys: HashSet<[u8; 32]>,
+    ) -> Self {
+        NodeIdentity {
+            signing_key,
+            network_secret,
+            trusted_keys: Mutex::new(trusted_keys),
+        }
+    }
+}
+
+/// Runs the mutual handshake over a freshly connected/accepted `stream`,
+/// verifying the peer proved both its private key and the shared network
+/// secret, and checking its public key against the configured membership set.
+/// Returns the peer's public key and the session key derived for sealing.
+async fn run_handshake(
+    stream: &mut TcpStream,
+    identity: &NodeIdentity,
+) -> Result<([u8; 32], [u8; 32]), Box<dyn Error + Send + Sync>> {
+    use ed25519_dalek::Signer;
+
+    let mut nonce = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+
+    let mut signed_material = Vec::with_capacity(64);
+    signed_material.extend_from_slice(&nonce);
+    signed_material.extend_from_slice(&identity.network_secret);
+    let signature = identity.signing_key.sign(&signed_material);
+
+    let outgoing = HandshakeMessage {
+        public_key: identity.signing_key.verifying_key().to_bytes(),
+        nonce,
+        signature: signature.to_bytes(),
+    };
+    stream.write_all(&outgoing.to_bytes()).await?;
+
+    let mut incoming_buf = [0u8; HANDSHAKE_MESSAGE_LEN];
+    stream.read_exact(&mut incoming_buf).await?;
+    let incoming = HandshakeMessage::from_bytes(&incoming_buf);
+
+    let peer_key = ed25519_dalek::VerifyingKey::from_bytes(&incoming.public_key)
+        .map_err(|e| format!("invalid peer public key: {}", e))?;
+    let mut peer_signed_material = Vec::with_capacity(64);
+    peer_signed_material.extend_from_slice(&incoming.nonce);
+    peer_signed_material.extend_from_slice(&identity.network_secret);
+    use ed25519_dalek::Verifier;
+    peer_key
+        .verify(
+            &peer_signed_material,
+            &ed25519_dalek::Signature::from_bytes(&incoming.signature),
+        )
+        .map_err(|_| "peer failed to prove knowledge of the network secret")?;
+
+    if !identity
+        .trusted_keys
+        .lock()
+        .await
+        .contains(&incoming.public_key)
+    {
+        return Err("peer public key is not a member of this cluster".into());
+    }
+
+    let session_key = derive_session_key(&identity.network_secret, &nonce, &incoming.nonce);
+    Ok((incoming.public_key, session_key))
+}
+
+/// Derives a symmetric session key from the shared network secret and both
+/// sides' handshake nonces, ordered canonically so client and server compute
+/// the same key regardless of which side called `run_handshake` "first".
+fn derive_session_key(network_secret: &[u8; 32], nonce_a: &[u8; 32], nonce_b: &[u8; 32]) -> [u8; 32] {
+    use sha2::{Digest, Sha256};
+    let (first, second) = if nonce_a <= nonce_b {
+        (nonce_a, nonce_b)
+    } else {
+        (nonce_b, nonce_a)
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(network_secret);
+    hasher.update(first);
+    hasher.update(second);
+    hasher.finalize().into()
+}
+
+/// Seals `data` under `session_key`, prefixing the random 12-byte AEAD nonce
+/// used for this frame ahead of the ciphertext and authentication tag.
+fn seal(session_key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    use chacha20poly1305::aead::{Aead, KeyInit};
+    use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(session_key));
+    let mut nonce_bytes = [0u8; 12];
+    rand::rngs::OsRng.fill_bytes(&mut nonce_bytes);
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce_bytes), data)
+        .map_err(|e| format!("failed to seal frame: {}", e))?;
+    let mut sealed = Vec::with_capacity(nonce_bytes.len() + ciphertext.len());
+    sealed.extend_from_slice(&nonce_bytes);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// Reverses [`seal`], splitting off the leading 12-byte nonce before decrypting.
+fn open(session_key: &[u8; 32], sealed: &[u8]) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    use chacha20poly1305::aead::{Aead, KeyInit};
+    use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+    if sealed.len() < 12 {
+        return Err("sealed frame shorter than its nonce".into());
+    }
+    let (nonce_bytes, ciphertext) = sealed.split_at(12);
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(session_key));
+    cipher
+        .decrypt(Nonce::from_slice(nonce_bytes), ciphertext)
+        .map_err(|e| format!("failed to open sealed frame: {}", e).into())
+}
 
 #[async_trait]
 pub trait NetworkLayer: Send + Sync {
@@ -21,6 +366,12 @@ pub trait NetworkLayer: Send + Sync {
         port: &str,
         data: &[u8],
     ) -> Result<(), Box<dyn Error + Send + Sync>>;
+    /// Returns the next inbound message. `TCPManager` returns an `Err` if a
+    /// connection delivered a malformed frame (oversize length prefix, or a
+    /// non-EOF I/O failure partway through one); there a peer simply closing
+    /// its connection is not an error and just makes that connection stop
+    /// contributing messages. `TlsManager` reads one frame per accepted
+    /// connection and returns `FramingError::UnexpectedEof` if it closes first.
     async fn receive(&self) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>>;
     async fn broadcast(
         &self,
@@ -29,48 +380,492 @@ pub trait NetworkLayer: Send + Sync {
     ) -> Result<(), Box<dyn Error + Send + Sync>>;
     async fn open(&self) -> Result<(), Box<dyn Error + Send + Sync>>;
     async fn close(&self) -> Result<(), Box<dyn Error + Send + Sync>>;
+
+    /// Continuous stream of inbound messages paired with the sender's address,
+    /// so the Raft event loop can `select!` over inbound traffic alongside its
+    /// timers instead of blocking on a single `receive`. The default just
+    /// repeats `receive` and pairs each payload with `0.0.0.0:0`, for
+    /// transports that don't yet track the originating peer per message;
+    /// `TCPManager`'s accept loop overrides this with the real peer address.
+    fn incoming(&self) -> Pin<Box<dyn Stream<Item = (SocketAddr, Vec<u8>)> + Send + '_>> {
+        Box::pin(futures::stream::unfold(self, |this| async move {
+            match this.receive().await {
+                Ok(data) => {
+                    let unspecified: SocketAddr = ([0, 0, 0, 0], 0).into();
+                    Some(((unspecified, data), this))
+                }
+                Err(_) => None,
+            }
+        }))
+    }
 }
 
-#[derive(Debug, Clone)]
+/// Capacity of the channel the accept loop feeds; `incoming`/`receive` pull
+/// from the other end, so this bounds how many received messages may sit
+/// unconsumed before the accept loop's channel send backs up.
+const INCOMING_CHANNEL_CAPACITY: usize = 1024;
+
+/// One slot in the accept loop's channel: a received request, or a framing
+/// error (oversize frame, non-EOF I/O failure) that ended the connection it
+/// came from and is forwarded so `receive`/`incoming` can surface it instead
+/// of silently looking like no traffic arrived.
+type IncomingItem = Result<(SocketAddr, RequestId, Vec<u8>), Box<dyn Error + Send + Sync>>;
+
+#[derive(Clone)]
 pub struct TCPManager {
     address: String,
     port: u16,
-    listener: Arc<Mutex<Option<TcpListener>>>,
+    listener: Arc<Mutex<Option<Arc<TcpListener>>>>,
+    accept_shutdown: Arc<Mutex<Option<CancellationToken>>>,
+    incoming_tx: mpsc::Sender<IncomingItem>,
+    incoming_rx: Arc<Mutex<mpsc::Receiver<IncomingItem>>>,
     is_open: Arc<Mutex<bool>>,
+    max_frame_size: u32,
+    connections: Arc<Mutex<HashMap<SocketAddr, Arc<Connection>>>>,
+    /// Every accepted connection that is still open, keyed by peer address, so
+    /// `respond` can write the reply back on the exact socket the request
+    /// arrived on instead of dialing a new one.
+    accepted_connections: Arc<Mutex<HashMap<SocketAddr, Arc<Connection>>>>,
+    /// Holds the `OutstandingGuard` for each inbound request that has been
+    /// forwarded to a caller but not yet answered, so `close`'s drain waits
+    /// for `respond` to actually write the reply rather than just for the
+    /// request to reach the channel.
+    pending_replies: Arc<Mutex<HashMap<(SocketAddr, RequestId), OutstandingGuard>>>,
+    next_request_id: Arc<AtomicU32>,
+    identity: Option<Arc<NodeIdentity>>,
+    remote_identities: Arc<Mutex<HashMap<SocketAddr, [u8; 32]>>>,
+    outstanding: Arc<AtomicU32>,
+    drain_notify: Arc<Notify>,
+    drain_timeout: Duration,
     log: slog::Logger,
 }
 
+impl fmt::Debug for TCPManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TCPManager")
+            .field("address", &self.address)
+            .field("port", &self.port)
+            .field("max_frame_size", &self.max_frame_size)
+            .finish()
+    }
+}
+
 impl TCPManager {
     pub fn new(address: String, port: u16, log: slog::Logger) -> Self {
+        let (incoming_tx, incoming_rx) = mpsc::channel(INCOMING_CHANNEL_CAPACITY);
         TCPManager {
             address,
             port,
             listener: Arc::new(Mutex::new(None)),
+            accept_shutdown: Arc::new(Mutex::new(None)),
+            incoming_tx,
+            incoming_rx: Arc::new(Mutex::new(incoming_rx)),
             is_open: Arc::new(Mutex::new(false)),
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            connections: Arc::new(Mutex::new(HashMap::new())),
+            accepted_connections: Arc::new(Mutex::new(HashMap::new())),
+            pending_replies: Arc::new(Mutex::new(HashMap::new())),
+            next_request_id: Arc::new(AtomicU32::new(0)),
+            identity: None,
+            remote_identities: Arc::new(Mutex::new(HashMap::new())),
+            outstanding: Arc::new(AtomicU32::new(0)),
+            drain_notify: Arc::new(Notify::new()),
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
             log,
         }
     }
 
-    async fn async_send(
+    /// Overrides the maximum accepted frame size, rejecting any inbound length
+    /// prefix larger than `max_frame_size` bytes instead of the default cap.
+    pub fn with_max_frame_size(mut self, max_frame_size: u32) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    /// Overrides how long `close` waits for outstanding request/response
+    /// exchanges to drain before returning an error instead of the default.
+    pub fn with_drain_timeout(mut self, drain_timeout: Duration) -> Self {
+        self.drain_timeout = drain_timeout;
+        self
+    }
+
+    /// Enables the ed25519 handshake: every connect/accept now proves both
+    /// sides' identity and the shared network secret before any Raft frame
+    /// is exchanged, and subsequent frames are sealed with the derived key.
+    pub fn with_identity(mut self, identity: NodeIdentity) -> Self {
+        self.identity = Some(Arc::new(identity));
+        self
+    }
+
+    /// Returns the verified public key of the peer at `address`, once a
+    /// handshake with it has completed. `None` if no handshake has run yet
+    /// (including when `with_identity` was never called).
+    pub async fn remote_public_key(&self, address: SocketAddr) -> Option<[u8; 32]> {
+        self.remote_identities.lock().await.get(&address).copied()
+    }
+
+    /// Binds the configured address and marks the manager open, without
+    /// starting the plaintext accept loop. Used directly by transports (TLS,
+    /// WebSocket) that wrap a `TCPManager` for its listener but dial and run
+    /// their own accept handling instead of `run_accept_loop`.
+    async fn bind_listener(&self) -> Result<Arc<TcpListener>, Box<dyn Error + Send + Sync>> {
+        let mut is_open = self.is_open.lock().await;
+        if *is_open {
+            return Err("Listener is already open".into());
+        }
+        let addr: SocketAddr = format!("{}:{}", self.address, self.port).parse()?;
+        let listener = Arc::new(TcpListener::bind(addr).await?);
+        *self.listener.lock().await = Some(listener.clone());
+        *is_open = true;
+        info!(self.log, "Listening on {}", addr);
+        Ok(listener)
+    }
+
+    /// Sends `data` to `address` over the pooled connection as a fire-and-forget
+    /// request (no reply is awaited), so heartbeat/AppendEntries-style traffic
+    /// reuses the same long-lived, already-handshaked socket `send_and_wait`
+    /// does instead of paying for a fresh connect on every call.
+    async fn send_pooled(
+        &self,
         data: &[u8],
         address: SocketAddr,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
-        let mut stream = TcpStream::connect(address).await?;
-        stream.write_all(data).await?;
+        let conn = self.get_connection(address).await?;
+        let request_id = self.next_request_id.fetch_add(1, Ordering::SeqCst);
+        let write_result = {
+            let mut writer = conn.writer.lock().await;
+            write_envelope(&mut *writer, &conn.session_key, request_id, false, data).await
+        };
+        if let Err(e) = write_result {
+            // The connection is dead; drop it so the next call reconnects lazily.
+            self.connections.lock().await.remove(&address);
+            return Err(e);
+        }
         Ok(())
     }
 
-    async fn handle_receive(&self) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
-        let mut data = Vec::new();
-        let listener = self.listener.lock().await;
-        if let Some(listener) = &*listener {
-            let (mut stream, _) = listener.accept().await?;
-            let mut buffer = Vec::new();
-            let mut reader = tokio::io::BufReader::new(&mut stream);
-            reader.read_to_end(&mut buffer).await?;
-            data = buffer;
+    /// Background task spawned by `open`: continuously accepts connections on
+    /// `listener` and spawns `run_accept_connection` for each one, so every
+    /// accepted peer keeps being read from instead of being dropped after one
+    /// frame. Exits when `shutdown` is cancelled or the listener errors out.
+    async fn run_accept_loop(
+        listener: Arc<TcpListener>,
+        shutdown: CancellationToken,
+        tx: mpsc::Sender<IncomingItem>,
+        max_frame_size: u32,
+        identity: Option<Arc<NodeIdentity>>,
+        remote_identities: Arc<Mutex<HashMap<SocketAddr, [u8; 32]>>>,
+        accepted_connections: Arc<Mutex<HashMap<SocketAddr, Arc<Connection>>>>,
+        pending_replies: Arc<Mutex<HashMap<(SocketAddr, RequestId), OutstandingGuard>>>,
+        outstanding: Arc<AtomicU32>,
+        drain_notify: Arc<Notify>,
+        log: slog::Logger,
+    ) {
+        loop {
+            let (stream, peer_addr) = tokio::select! {
+                _ = shutdown.cancelled() => break,
+                result = listener.accept() => match result {
+                    Ok(pair) => pair,
+                    Err(e) => {
+                        warn!(log, "accept loop exiting: {}", e);
+                        break;
+                    }
+                },
+            };
+            tokio::spawn(Self::run_accept_connection(
+                stream,
+                peer_addr,
+                tx.clone(),
+                max_frame_size,
+                identity.clone(),
+                remote_identities.clone(),
+                accepted_connections.clone(),
+                pending_replies.clone(),
+                outstanding.clone(),
+                drain_notify.clone(),
+                log.clone(),
+            ));
         }
-        Ok(data)
+    }
+
+    /// Per-connection task spawned by `run_accept_loop`: handshakes once (if
+    /// configured), registers the connection's write half in
+    /// `accepted_connections` so `respond` can reply on it, then reads every
+    /// frame the peer sends for as long as it stays open. Each request's
+    /// `(peer_addr, request_id, payload)` is pushed into `tx` for
+    /// `incoming`/`receive`/`incoming_requests` to drain. A framing error ends
+    /// the connection (frame boundaries can no longer be trusted) and is
+    /// forwarded as an `Err` item so it reaches `receive`/`incoming`; the peer
+    /// simply closing the connection is not forwarded as an error.
+    async fn run_accept_connection(
+        mut stream: TcpStream,
+        peer_addr: SocketAddr,
+        tx: mpsc::Sender<IncomingItem>,
+        max_frame_size: u32,
+        identity: Option<Arc<NodeIdentity>>,
+        remote_identities: Arc<Mutex<HashMap<SocketAddr, [u8; 32]>>>,
+        accepted_connections: Arc<Mutex<HashMap<SocketAddr, Arc<Connection>>>>,
+        pending_replies: Arc<Mutex<HashMap<(SocketAddr, RequestId), OutstandingGuard>>>,
+        outstanding: Arc<AtomicU32>,
+        drain_notify: Arc<Notify>,
+        log: slog::Logger,
+    ) {
+        let session_key = match &identity {
+            Some(identity) => match run_handshake(&mut stream, identity).await {
+                Ok((peer_key, session_key)) => {
+                    remote_identities.lock().await.insert(peer_addr, peer_key);
+                    Some(session_key)
+                }
+                Err(e) => {
+                    warn!(log, "handshake with {} failed: {}", peer_addr, e);
+                    return;
+                }
+            },
+            None => None,
+        };
+        let (mut read_half, write_half) = stream.into_split();
+        let connection = Arc::new(Connection {
+            writer: Mutex::new(write_half),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            session_key,
+        });
+        accepted_connections
+            .lock()
+            .await
+            .insert(peer_addr, connection);
+
+        loop {
+            let frame = match read_frame(&mut read_half, max_frame_size).await {
+                Ok(frame) => frame,
+                Err(e) => {
+                    let clean_eof = matches!(
+                        e.downcast_ref::<FramingError>(),
+                        Some(FramingError::UnexpectedEof)
+                    );
+                    if clean_eof {
+                        warn!(log, "accepted connection from {} closed: {}", peer_addr, e);
+                    } else {
+                        warn!(log, "framing error on connection from {}: {}", peer_addr, e);
+                        let _ = tx.send(Err(e)).await;
+                    }
+                    break;
+                }
+            };
+            // Guard only the processing of a frame we've already received, not
+            // the wait for the next one, so an idle persistent connection never
+            // blocks `close`'s drain on traffic that isn't actually in flight.
+            let guard = OutstandingGuard::new(outstanding.clone(), drain_notify.clone());
+            let envelope = match &session_key {
+                Some(key) => match open(key, &frame) {
+                    Ok(envelope) => envelope,
+                    Err(e) => {
+                        warn!(log, "dropping unsealable frame from {}: {}", peer_addr, e);
+                        continue;
+                    }
+                },
+                None => frame,
+            };
+            let (request_id, is_response, payload) = match decode_envelope(envelope) {
+                Ok(parts) => parts,
+                Err(e) => {
+                    warn!(log, "dropping malformed frame from {}: {}", peer_addr, e);
+                    continue;
+                }
+            };
+            if is_response {
+                warn!(log, "dropping unexpected response envelope from {}", peer_addr);
+                continue;
+            }
+            // Held until `respond` writes (or fails to write) the reply for this
+            // request, so `close`'s drain waits for the exchange to actually
+            // finish instead of just for the request to reach `tx`.
+            pending_replies
+                .lock()
+                .await
+                .insert((peer_addr, request_id), guard);
+            if tx.send(Ok((peer_addr, request_id, payload))).await.is_err() {
+                pending_replies.lock().await.remove(&(peer_addr, request_id));
+                break;
+            }
+        }
+        accepted_connections.lock().await.remove(&peer_addr);
+        pending_replies
+            .lock()
+            .await
+            .retain(|(addr, _), _| *addr != peer_addr);
+    }
+
+    /// Returns the pooled connection for `address`, lazily dialing (or
+    /// redialing, after a prior failure) and spawning its background reader.
+    /// When this manager has an identity configured, the handshake runs once
+    /// up front and every subsequent frame on the connection is sealed with it.
+    async fn get_connection(
+        &self,
+        address: SocketAddr,
+    ) -> Result<Arc<Connection>, Box<dyn Error + Send + Sync>> {
+        if let Some(conn) = self.connections.lock().await.get(&address) {
+            return Ok(conn.clone());
+        }
+        // Dial and handshake without holding `connections`, so one slow/stuck
+        // peer can't serialize every other concurrent dial behind it. Two
+        // callers can race to dial the same address; whichever inserts first
+        // wins and the loser's connection is dropped in favor of the winner's.
+        let mut stream = TcpStream::connect(address).await?;
+        let session_key = match &self.identity {
+            Some(identity) => {
+                let (peer_key, session_key) = run_handshake(&mut stream, identity).await?;
+                self.remote_identities
+                    .lock()
+                    .await
+                    .insert(address, peer_key);
+                Some(session_key)
+            }
+            None => None,
+        };
+        let (read_half, write_half) = stream.into_split();
+        let pending = Arc::new(Mutex::new(HashMap::new()));
+        let conn = Arc::new(Connection {
+            writer: Mutex::new(write_half),
+            pending: pending.clone(),
+            session_key,
+        });
+
+        let mut connections = self.connections.lock().await;
+        if let Some(existing) = connections.get(&address) {
+            return Ok(existing.clone());
+        }
+        tokio::spawn(Self::run_reader(
+            read_half,
+            pending,
+            self.max_frame_size,
+            session_key,
+            self.log.clone(),
+        ));
+        connections.insert(address, conn.clone());
+        Ok(conn)
+    }
+
+    /// Background task owning a connection's read half: demultiplexes incoming
+    /// frames and hands each response payload to the waiting `send_and_wait` caller.
+    async fn run_reader(
+        mut read_half: OwnedReadHalf,
+        pending: Arc<Mutex<HashMap<RequestId, oneshot::Sender<Vec<u8>>>>>,
+        max_frame_size: u32,
+        session_key: Option<[u8; 32]>,
+        log: slog::Logger,
+    ) {
+        loop {
+            let frame = match read_frame(&mut read_half, max_frame_size).await {
+                Ok(frame) => frame,
+                Err(e) => {
+                    warn!(log, "connection reader exiting: {}", e);
+                    break;
+                }
+            };
+            let envelope = match &session_key {
+                Some(key) => match open(key, &frame) {
+                    Ok(envelope) => envelope,
+                    Err(e) => {
+                        warn!(log, "dropping unsealable frame: {}", e);
+                        continue;
+                    }
+                },
+                None => frame,
+            };
+            let (request_id, is_response, payload) = match decode_envelope(envelope) {
+                Ok(parts) => parts,
+                Err(e) => {
+                    warn!(log, "dropping malformed frame: {}", e);
+                    continue;
+                }
+            };
+            if is_response {
+                if let Some(sender) = pending.lock().await.remove(&request_id) {
+                    let _ = sender.send(payload);
+                }
+            }
+        }
+    }
+
+    /// Sends `data` to `address` over the pooled connection and awaits the
+    /// matching response, correlated by its multiplexed request id.
+    pub async fn send_and_wait(
+        &self,
+        address: &str,
+        port: &str,
+        data: &[u8],
+    ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        let addr: SocketAddr = format!("{}:{}", address, port).parse()?;
+        let _guard = OutstandingGuard::new(self.outstanding.clone(), self.drain_notify.clone());
+        let conn = self.get_connection(addr).await?;
+        let request_id = self.next_request_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+        conn.pending.lock().await.insert(request_id, tx);
+        let write_result = {
+            let mut writer = conn.writer.lock().await;
+            write_envelope(&mut *writer, &conn.session_key, request_id, false, data).await
+        };
+        if let Err(e) = write_result {
+            // The connection is dead; drop it so the next call reconnects lazily.
+            self.connections.lock().await.remove(&addr);
+            conn.pending.lock().await.remove(&request_id);
+            return Err(e);
+        }
+        Ok(rx.await?)
+    }
+
+    /// Stream of inbound requests that haven't been answered yet, each paired
+    /// with the peer's address and the `request_id` `respond` must echo back.
+    /// Unlike `NetworkLayer::incoming` (which drops the id for transports that
+    /// don't support replying), this lets a caller answer the multiplexed
+    /// requests `send_and_wait` sends from another `TCPManager`.
+    pub fn incoming_requests(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = (SocketAddr, RequestId, Vec<u8>)> + Send + 'static>> {
+        let incoming_rx = self.incoming_rx.clone();
+        let log = self.log.clone();
+        Box::pin(futures::stream::unfold(
+            (incoming_rx, log),
+            |(incoming_rx, log)| async move {
+                loop {
+                    match incoming_rx.lock().await.recv().await {
+                        Some(Ok(item)) => return Some((item, (incoming_rx, log))),
+                        Some(Err(e)) => {
+                            warn!(log, "dropping framing error from incoming stream: {}", e);
+                            continue;
+                        }
+                        None => return None,
+                    }
+                }
+            },
+        ))
+    }
+
+    /// Delivers `data` back to the peer that sent `request_id`, over the exact
+    /// accepted connection `run_accept_connection` registered for it, sealing with
+    /// that connection's session key if a handshake ran. Pairs with
+    /// `incoming_requests` to answer a `send_and_wait` caller on another
+    /// `TCPManager`.
+    pub async fn respond(
+        &self,
+        peer: SocketAddr,
+        request_id: RequestId,
+        data: &[u8],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let conn = self
+            .accepted_connections
+            .lock()
+            .await
+            .get(&peer)
+            .cloned()
+            .ok_or("no inbound connection open for that peer")?;
+        let mut writer = conn.writer.lock().await;
+        let result = write_envelope(&mut *writer, &conn.session_key, request_id, true, data).await;
+        // Dropped only now, releasing the `OutstandingGuard` `run_accept_connection`
+        // has been holding for this request since it was forwarded to `tx`, whether
+        // the write above succeeded or failed.
+        self.pending_replies.lock().await.remove(&(peer, request_id));
+        result
     }
 }
 
@@ -83,12 +878,36 @@ impl NetworkLayer for TCPManager {
         data: &[u8],
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
         let addr: SocketAddr = format!("{}:{}", address, port).parse()?;
-        Self::async_send(data, addr).await?;
+        self.send_pooled(data, addr).await?;
         Ok(())
     }
 
     async fn receive(&self) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
-        self.handle_receive().await
+        match self.incoming_rx.lock().await.recv().await {
+            Some(Ok((_, _, data))) => Ok(data),
+            Some(Err(e)) => Err(e),
+            None => Err("incoming channel closed".into()),
+        }
+    }
+
+    fn incoming(&self) -> Pin<Box<dyn Stream<Item = (SocketAddr, Vec<u8>)> + Send + '_>> {
+        let incoming_rx = self.incoming_rx.clone();
+        let log = self.log.clone();
+        Box::pin(futures::stream::unfold(
+            (incoming_rx, log),
+            |(incoming_rx, log)| async move {
+                loop {
+                    match incoming_rx.lock().await.recv().await {
+                        Some(Ok((addr, _, data))) => return Some(((addr, data), (incoming_rx, log))),
+                        Some(Err(e)) => {
+                            warn!(log, "dropping framing error from incoming stream: {}", e);
+                            continue;
+                        }
+                        None => return None,
+                    }
+                }
+            },
+        ))
     }
 
     async fn broadcast(
@@ -99,7 +918,7 @@ impl NetworkLayer for TCPManager {
         let futures = addresses.into_iter().map(|address| {
             let (ip, port) = parse_ip_address(&address);
             let addr: SocketAddr = format!("{}:{}", ip, port).parse().unwrap();
-            Self::async_send(data, addr)
+            self.send_pooled(data, addr)
         });
         join_all(futures)
             .await
@@ -109,15 +928,22 @@ impl NetworkLayer for TCPManager {
     }
 
     async fn open(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
-        let mut is_open = self.is_open.lock().await;
-        if *is_open {
-            return Err("Listener is already open".into());
-        }
-        let addr: SocketAddr = format!("{}:{}", self.address, self.port).parse()?;
-        let listener = TcpListener::bind(addr).await?;
-        *self.listener.lock().await = Some(listener);
-        *is_open = true;
-        info!(self.log, "Listening on {}", addr);
+        let listener = self.bind_listener().await?;
+        let shutdown = CancellationToken::new();
+        *self.accept_shutdown.lock().await = Some(shutdown.clone());
+        tokio::spawn(Self::run_accept_loop(
+            listener,
+            shutdown,
+            self.incoming_tx.clone(),
+            self.max_frame_size,
+            self.identity.clone(),
+            self.remote_identities.clone(),
+            self.accepted_connections.clone(),
+            self.pending_replies.clone(),
+            self.outstanding.clone(),
+            self.drain_notify.clone(),
+            self.log.clone(),
+        ));
         Ok(())
     }
 
@@ -126,13 +952,286 @@ impl NetworkLayer for TCPManager {
         if !*is_open {
             return Err("Listener is not open".into());
         }
+
+        // Phase 1: stop accepting new connections.
         *self.listener.lock().await = None;
+        if let Some(shutdown) = self.accept_shutdown.lock().await.take() {
+            shutdown.cancel();
+        }
+        info!(self.log, "Draining before close");
+
+        // Phase 2: wait for outstanding request/response exchanges to finish.
+        // `notified()` is created before the count check, and the guard signals
+        // completion via `notify_one` (which stores a permit for the next
+        // `notified()` call even with no waiter registered yet), so a completion
+        // that races with it is never missed.
+        let deadline = tokio::time::Instant::now() + self.drain_timeout;
+        loop {
+            let notified = self.drain_notify.notified();
+            if self.outstanding.load(Ordering::SeqCst) == 0 {
+                break;
+            }
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            if remaining.is_zero() {
+                return Err(format!(
+                    "timed out after {:?} waiting for {} outstanding exchange(s) to finish",
+                    self.drain_timeout,
+                    self.outstanding.load(Ordering::SeqCst)
+                )
+                .into());
+            }
+            let _ = tokio::time::timeout(remaining, notified).await;
+        }
+
         *is_open = false;
         info!(self.log, "Listener closed");
         Ok(())
     }
 }
 
+/// TLS-encrypted transport built on [`TCPManager`]'s listener plumbing,
+/// behind the `tls` feature so plaintext clusters don't pay for the dependency.
+#[cfg(feature = "tls")]
+pub mod tls {
+    use super::*;
+    use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+    use tokio_rustls::rustls::{ClientConfig, RootCertStore, ServerConfig};
+    use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+    /// A `NetworkLayer` that wraps every accepted/dialed connection in TLS before
+    /// the length-prefix framing runs, so the Raft core stays transport-agnostic.
+    pub struct TlsManager {
+        inner: TCPManager,
+        acceptor: TlsAcceptor,
+        connector: TlsConnector,
+    }
+
+    impl TlsManager {
+        /// Builds a `TlsManager` from a server cert chain/key (presented to peers
+        /// that connect to us) and the set of CA roots we trust when dialing out.
+        pub fn new(
+            address: String,
+            port: u16,
+            log: slog::Logger,
+            server_cert_chain: Vec<CertificateDer<'static>>,
+            server_key: PrivateKeyDer<'static>,
+            trusted_roots: RootCertStore,
+        ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+            let server_config = ServerConfig::builder()
+                .with_no_client_auth()
+                .with_single_cert(server_cert_chain, server_key)?;
+            let client_config = ClientConfig::builder()
+                .with_root_certificates(trusted_roots)
+                .with_no_client_auth();
+
+            Ok(TlsManager {
+                inner: TCPManager::new(address, port, log),
+                acceptor: TlsAcceptor::from(Arc::new(server_config)),
+                connector: TlsConnector::from(Arc::new(client_config)),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl NetworkLayer for TlsManager {
+        async fn send(
+            &self,
+            address: &str,
+            port: &str,
+            data: &[u8],
+        ) -> Result<(), Box<dyn Error + Send + Sync>> {
+            let addr: SocketAddr = format!("{}:{}", address, port).parse()?;
+            let dns_name = ServerName::try_from(address.to_string())
+                .map_err(|e| format!("invalid DNS name {}: {}", address, e))?;
+            let stream = TcpStream::connect(addr).await?;
+            let mut tls_stream = self.connector.connect(dns_name, stream).await?;
+            write_frame(&mut tls_stream, data).await?;
+            Ok(())
+        }
+
+        async fn receive(&self) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+            let mut data = Vec::new();
+            let listener = self.inner.listener.lock().await.clone();
+            if let Some(listener) = listener {
+                let (stream, _) = listener.accept().await?;
+                let mut tls_stream = self.acceptor.accept(stream).await?;
+                data = read_frame(&mut tls_stream, self.inner.max_frame_size).await?;
+            }
+            Ok(data)
+        }
+
+        async fn broadcast(
+            &self,
+            data: &[u8],
+            addresses: Vec<String>,
+        ) -> Result<(), Box<dyn Error + Send + Sync>> {
+            let futures = addresses.into_iter().map(|address| {
+                let (ip, port) = parse_ip_address(&address);
+                self.send(&ip, &port, data)
+            });
+            join_all(futures)
+                .await
+                .into_iter()
+                .collect::<Result<_, _>>()?;
+            Ok(())
+        }
+
+        async fn open(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+            self.inner.bind_listener().await?;
+            Ok(())
+        }
+
+        async fn close(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+            self.inner.close().await
+        }
+    }
+}
+
+/// WebSocket transport built on [`TCPManager`]'s listener plumbing, for
+/// clusters that need to cross NATs or HTTP proxies where raw TCP is blocked.
+/// Each Raft message is one binary WebSocket frame, which gives message
+/// framing for free instead of the 4-byte length prefix `TCPManager` adds.
+#[cfg(feature = "websocket")]
+pub mod websocket {
+    use super::*;
+    use async_tungstenite::tokio::{accept_async, connect_async};
+    use async_tungstenite::tungstenite::Message;
+    use futures::{SinkExt, StreamExt};
+    use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+    use tokio_rustls::rustls::{ClientConfig, RootCertStore, ServerConfig};
+    use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+    /// TLS material for `WebSocketManager::with_tls`, mirroring how
+    /// [`super::tls::TlsManager`] is configured: a cert chain/key presented to
+    /// peers that connect to us, and the CA roots we trust when dialing out.
+    struct WebSocketTls {
+        acceptor: TlsAcceptor,
+        connector: TlsConnector,
+    }
+
+    /// A `NetworkLayer` that speaks ws/wss instead of raw length-prefixed TCP,
+    /// so it can be swapped in for [`TCPManager`] wherever a relay or proxy sits
+    /// between cluster members. Plain (no `with_tls`) speaks ws:// and does not
+    /// encrypt traffic; call `with_tls` for a wss:// relay that does.
+    pub struct WebSocketManager {
+        inner: TCPManager,
+        tls: Option<WebSocketTls>,
+    }
+
+    impl WebSocketManager {
+        pub fn new(address: String, port: u16, log: slog::Logger) -> Self {
+            WebSocketManager {
+                inner: TCPManager::new(address, port, log),
+                tls: None,
+            }
+        }
+
+        /// Wraps every dialed/accepted connection in TLS before the WebSocket
+        /// handshake runs, so `send`/`receive` speak wss:// instead of
+        /// plaintext ws://, for relays that sit outside the cluster's trust
+        /// boundary.
+        pub fn with_tls(
+            mut self,
+            server_cert_chain: Vec<CertificateDer<'static>>,
+            server_key: PrivateKeyDer<'static>,
+            trusted_roots: RootCertStore,
+        ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+            let server_config = ServerConfig::builder()
+                .with_no_client_auth()
+                .with_single_cert(server_cert_chain, server_key)?;
+            let client_config = ClientConfig::builder()
+                .with_root_certificates(trusted_roots)
+                .with_no_client_auth();
+            self.tls = Some(WebSocketTls {
+                acceptor: TlsAcceptor::from(Arc::new(server_config)),
+                connector: TlsConnector::from(Arc::new(client_config)),
+            });
+            Ok(self)
+        }
+    }
+
+    #[async_trait]
+    impl NetworkLayer for WebSocketManager {
+        async fn send(
+            &self,
+            address: &str,
+            port: &str,
+            data: &[u8],
+        ) -> Result<(), Box<dyn Error + Send + Sync>> {
+            match &self.tls {
+                Some(tls) => {
+                    let addr: SocketAddr = format!("{}:{}", address, port).parse()?;
+                    let dns_name = ServerName::try_from(address.to_string())
+                        .map_err(|e| format!("invalid DNS name {}: {}", address, e))?;
+                    let stream = TcpStream::connect(addr).await?;
+                    let tls_stream = tls.connector.connect(dns_name, stream).await?;
+                    let url = format!("wss://{}:{}", address, port);
+                    let (mut ws_stream, _) = async_tungstenite::client_async(url, tls_stream).await?;
+                    ws_stream.send(Message::Binary(data.to_vec())).await?;
+                    ws_stream.close(None).await?;
+                }
+                None => {
+                    let url = format!("ws://{}:{}", address, port);
+                    let (mut ws_stream, _) = connect_async(url).await?;
+                    ws_stream.send(Message::Binary(data.to_vec())).await?;
+                    ws_stream.close(None).await?;
+                }
+            }
+            Ok(())
+        }
+
+        async fn receive(&self) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+            let mut data = Vec::new();
+            let listener = self.inner.listener.lock().await.clone();
+            if let Some(listener) = listener {
+                let (stream, _) = listener.accept().await?;
+                let message = match &self.tls {
+                    Some(tls) => {
+                        let tls_stream = tls.acceptor.accept(stream).await?;
+                        let mut ws_stream = async_tungstenite::accept_async(tls_stream).await?;
+                        ws_stream.next().await
+                    }
+                    None => {
+                        let mut ws_stream = accept_async(stream).await?;
+                        ws_stream.next().await
+                    }
+                };
+                if let Some(message) = message {
+                    if let Message::Binary(bytes) = message? {
+                        data = bytes;
+                    }
+                }
+            }
+            Ok(data)
+        }
+
+        async fn broadcast(
+            &self,
+            data: &[u8],
+            addresses: Vec<String>,
+        ) -> Result<(), Box<dyn Error + Send + Sync>> {
+            let futures = addresses.into_iter().map(|address| {
+                let (ip, port) = parse_ip_address(&address);
+                self.send(&ip, &port, data)
+            });
+            join_all(futures)
+                .await
+                .into_iter()
+                .collect::<Result<_, _>>()?;
+            Ok(())
+        }
+
+        async fn open(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+            self.inner.bind_listener().await?;
+            Ok(())
+        }
+
+        async fn close(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+            self.inner.close().await
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use slog::{o, Drain};
@@ -166,4 +1265,187 @@ mod tests {
         network.send("127.0.0.1", "8082", &data).await.unwrap();
         handler.await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_receive_rejects_oversized_frame() {
+        let network = TCPManager::new("127.0.0.1".to_string(), 8083, get_logger())
+            .with_max_frame_size(2);
+        network.open().await.unwrap();
+        let network_clone = network.clone();
+        let handler = tokio::spawn(async move { network_clone.receive().await });
+        network
+            .send("127.0.0.1", "8083", &[1, 2, 3])
+            .await
+            .unwrap();
+        let result = handler.await.unwrap();
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_and_wait_multiplexed() {
+        let addr: SocketAddr = "127.0.0.1:8084".parse().unwrap();
+        let listener = TcpListener::bind(addr).await.unwrap();
+        // Accept every connection: two concurrent first calls may each dial
+        // before one wins the pool slot, and the winner's stream need not be
+        // the first one accepted here.
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let (mut read_half, mut write_half) = stream.into_split();
+                    loop {
+                        let envelope = match read_frame(&mut read_half, DEFAULT_MAX_FRAME_SIZE).await {
+                            Ok(envelope) => envelope,
+                            Err(_) => break,
+                        };
+                        let (request_id, _, payload) = decode_envelope(envelope).unwrap();
+                        let response = encode_envelope(request_id, true, &payload);
+                        write_frame(&mut write_half, &response).await.unwrap();
+                    }
+                });
+            }
+        });
+
+        let network = TCPManager::new("127.0.0.1".to_string(), 8085, get_logger());
+        let (a, b) = tokio::join!(
+            network.send_and_wait("127.0.0.1", "8084", &[1]),
+            network.send_and_wait("127.0.0.1", "8084", &[2])
+        );
+        assert_eq!(a.unwrap(), vec![1]);
+        assert_eq!(b.unwrap(), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn test_send_and_wait_against_real_tcp_manager_peer() {
+        use futures::StreamExt;
+
+        let server = TCPManager::new("127.0.0.1".to_string(), 8090, get_logger());
+        server.open().await.unwrap();
+        let server_clone = server.clone();
+        tokio::spawn(async move {
+            let mut requests = server_clone.incoming_requests();
+            while let Some((peer, request_id, payload)) = requests.next().await {
+                let mut response = payload;
+                response.push(b'!');
+                server_clone.respond(peer, request_id, &response).await.unwrap();
+            }
+        });
+
+        let client = TCPManager::new("127.0.0.1".to_string(), 0, get_logger());
+        let response = client
+            .send_and_wait("127.0.0.1", "8090", b"ping")
+            .await
+            .unwrap();
+        assert_eq!(response, b"ping!".to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_send_receive_with_identity_handshake() {
+        let server_signing_key = ed25519_dalek::SigningKey::from_bytes(&[1u8; 32]);
+        let client_signing_key = ed25519_dalek::SigningKey::from_bytes(&[2u8; 32]);
+        let network_secret = [9u8; 32];
+
+        let mut server_trusted = HashSet::new();
+        server_trusted.insert(client_signing_key.verifying_key().to_bytes());
+        let mut client_trusted = HashSet::new();
+        client_trusted.insert(server_signing_key.verifying_key().to_bytes());
+
+        let server = TCPManager::new("127.0.0.1".to_string(), 8086, get_logger())
+            .with_identity(NodeIdentity::new(server_signing_key.clone(), network_secret, server_trusted));
+        let client = TCPManager::new("127.0.0.1".to_string(), 0, get_logger())
+            .with_identity(NodeIdentity::new(client_signing_key, network_secret, client_trusted));
+
+        server.open().await.unwrap();
+        let server_clone = server.clone();
+        let handler = tokio::spawn(async move { server_clone.receive().await });
+        let server_addr: SocketAddr = "127.0.0.1:8086".parse().unwrap();
+        client.send("127.0.0.1", "8086", &[7, 8, 9]).await.unwrap();
+
+        let data = handler.await.unwrap().unwrap();
+        assert_eq!(data, vec![7, 8, 9]);
+        assert_eq!(
+            client.remote_public_key(server_addr).await,
+            Some(server_signing_key.verifying_key().to_bytes())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_close_waits_for_outstanding_exchanges() {
+        let network = TCPManager::new("127.0.0.1".to_string(), 8088, get_logger())
+            .with_drain_timeout(std::time::Duration::from_millis(50));
+        network.open().await.unwrap();
+
+        let guard = OutstandingGuard::new(network.outstanding.clone(), network.drain_notify.clone());
+        assert!(network.close().await.is_err());
+
+        drop(guard);
+        network.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_close_waits_for_respond_not_just_delivery() {
+        use futures::StreamExt;
+
+        let server = TCPManager::new("127.0.0.1".to_string(), 8091, get_logger())
+            .with_drain_timeout(std::time::Duration::from_millis(50));
+        server.open().await.unwrap();
+
+        let client = TCPManager::new("127.0.0.1".to_string(), 0, get_logger());
+        let request = tokio::spawn(async move {
+            client.send_and_wait("127.0.0.1", "8091", b"ping").await
+        });
+
+        let mut requests = server.incoming_requests();
+        let (peer, request_id, payload) = requests.next().await.unwrap();
+        assert_eq!(payload, b"ping".to_vec());
+
+        // The request has been delivered but not yet answered: `close` must
+        // still treat it as outstanding rather than considering it finished
+        // the moment it reached `incoming_requests`.
+        assert!(server.close().await.is_err());
+
+        server.respond(peer, request_id, b"pong").await.unwrap();
+        assert_eq!(request.await.unwrap().unwrap(), b"pong".to_vec());
+    }
+
+    // Multi-threaded so the guard drop below can genuinely race with `close`'s
+    // count check/`notified()` registration on another worker thread, instead of
+    // interleaving deterministically on a single-threaded executor.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_close_does_not_stall_when_guard_drops_mid_check() {
+        let network = TCPManager::new("127.0.0.1".to_string(), 8087, get_logger())
+            .with_drain_timeout(std::time::Duration::from_secs(10));
+        network.open().await.unwrap();
+
+        let guard = OutstandingGuard::new(network.outstanding.clone(), network.drain_notify.clone());
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            drop(guard);
+        });
+
+        let start = tokio::time::Instant::now();
+        network.close().await.unwrap();
+        assert!(
+            start.elapsed() < std::time::Duration::from_secs(5),
+            "close should wake promptly once the last exchange finishes, not stall toward drain_timeout"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_incoming_stream_yields_messages_with_peer_addr() {
+        use futures::StreamExt;
+
+        let network = TCPManager::new("127.0.0.1".to_string(), 8089, get_logger());
+        network.open().await.unwrap();
+        let mut incoming = network.incoming();
+
+        network.send("127.0.0.1", "8089", &[1, 2, 3]).await.unwrap();
+        let (peer_addr, data) = incoming.next().await.unwrap();
+        assert_eq!(data, vec![1, 2, 3]);
+        assert_eq!(peer_addr.ip().to_string(), "127.0.0.1");
+
+        network.send("127.0.0.1", "8089", &[4, 5, 6]).await.unwrap();
+        let (_, data) = incoming.next().await.unwrap();
+        assert_eq!(data, vec![4, 5, 6]);
+    }
 }